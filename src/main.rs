@@ -1,3 +1,7 @@
+use futures::stream::TryStreamExt;
+use log::{error, info, warn, LevelFilter};
+use netlink_packet_route::address::{AddressAttribute, AddressHeaderFlags, AddressScope};
+use netlink_packet_route::AddressFamily;
 use regex::Regex;
 use reqwest::{
     header::{HeaderMap, HeaderValue, AUTHORIZATION},
@@ -5,17 +9,63 @@ use reqwest::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::io::Write;
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize)]
 struct Config {
     auth_email: String,
     auth_method: String,
     auth_key: String,
+    zones: Vec<ZoneConfig>,
+    interval_seconds: Option<u64>,
+    #[serde(default)]
+    ip_source: IpSource,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(tag = "source", rename_all = "lowercase")]
+enum IpSource {
+    #[default]
+    Http,
+    Netlink {
+        interface: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct ZoneConfig {
     zone_identifier: String,
-    record_name: String,
+    records: Vec<RecordConfig>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordConfig {
+    name: String,
+    record_types: Vec<RecordType>,
     ttl: u32,
     proxy: bool,
+    #[serde(default)]
+    create_if_missing: bool,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+enum RecordType {
+    A,
+    AAAA,
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordType::A => write!(f, "A"),
+            RecordType::AAAA => write!(f, "AAAA"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -28,24 +78,16 @@ struct Payload {
 }
 
 const IPV4_REGEX: &str = r#"([01]?[0-9]?[0-9]|2[0-4][0-9]|25[0-5])\.([01]?[0-9]?[0-9]|2[0-4][0-9]|25[0-5])\.([01]?[0-9]?[0-9]|2[0-4][0-9]|25[0-5])\.([01]?[0-9]?[0-9]|2[0-4][0-9]|25[0-5])"#;
+const IPV6_REGEX: &str = r#"([0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,7}:|([0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,5}(:[0-9a-fA-F]{1,4}){1,2}|([0-9a-fA-F]{1,4}:){1,4}(:[0-9a-fA-F]{1,4}){1,3}|([0-9a-fA-F]{1,4}:){1,3}(:[0-9a-fA-F]{1,4}){1,4}|([0-9a-fA-F]{1,4}:){1,2}(:[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:((:[0-9a-fA-F]{1,4}){1,6})|:((:[0-9a-fA-F]{1,4}){1,7}|:)"#;
 const CLOUDFLARE_URL: &str = "https://cloudflare.com/cdn-cgi/trace";
 const IPIFY_URL: &str = "https://api.ipify.org";
 const ICANHAZIP_URL: &str = "https://ipv4.icanhazip.com";
+const IPIFY6_URL: &str = "https://api6.ipify.org";
+const DEFAULT_INTERVAL_SECONDS: u64 = 300;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config_path = format!(
-        "{}/.config/ddns/config.json",
-        std::env::var("HOME").unwrap()
-    );
-    let config_contents = fs::read_to_string(&config_path).expect(&format!(
-        "Could not read the config file.\nconfig: {}",
-        config_path
-    ));
-    let config: Config = serde_json::from_str(&config_contents)
-        .expect(&format!("Invalid config file.\nconfig: {}", config_path));
-
-    // Get the ip from Cloudflare
+// Resolve the host's public IPv4 address via Cloudflare's trace endpoint,
+// falling back to ipify/icanhazip if Cloudflare can't be reached.
+async fn resolve_ipv4() -> Result<String, Box<dyn std::error::Error>> {
     let ip = match reqwest::get(CLOUDFLARE_URL).await {
         Ok(response) => {
             let body = response.text().await?;
@@ -79,12 +121,368 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    // Use regex to check for proper IPv4 format.
     if !Regex::new(&format!("^{}$", IPV4_REGEX))?.is_match(&ip) {
-        eprintln!("ddns updater: Failed to find a valid IP.");
-        std::process::exit(2);
+        return Err("Failed to find a valid IPv4 address.".into());
+    }
+
+    Ok(ip)
+}
+
+// Resolve the host's public IPv6 address from ipify's IPv6-only endpoint.
+async fn resolve_ipv6() -> Result<String, Box<dyn std::error::Error>> {
+    let ip = reqwest::get(IPIFY6_URL).await?.text().await?;
+    let ip = ip.trim().to_owned();
+
+    if !Regex::new(&format!("^(?:{})$", IPV6_REGEX))?.is_match(&ip) {
+        return Err("Failed to find a valid IPv6 address.".into());
+    }
+
+    Ok(ip)
+}
+
+// Read the named interface's global-scope address straight from the kernel
+// via netlink, instead of round-tripping to an external HTTP service.
+async fn resolve_from_netlink(
+    interface: &str,
+    record_type: RecordType,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let (connection, handle, _) = rtnetlink::new_connection()?;
+    tokio::spawn(connection);
+
+    let mut links = handle.link().get().match_name(interface.to_owned()).execute();
+    let link = links
+        .try_next()
+        .await?
+        .ok_or_else(|| format!("interface {} not found", interface))?;
+
+    let family = match record_type {
+        RecordType::A => AddressFamily::Inet,
+        RecordType::AAAA => AddressFamily::Inet6,
+    };
+
+    // IPv6 interfaces routinely carry RFC 4941 temporary/privacy addresses
+    // alongside the stable one. Prefer a permanent, non-deprecated address
+    // so `last_seen` doesn't see a "change" every time the privacy address
+    // rotates; only fall back to a temporary address if no permanent one
+    // is found.
+    let mut addresses = handle
+        .address()
+        .get()
+        .set_link_index_filter(link.header.index)
+        .execute();
+    let mut fallback = None;
+    while let Some(msg) = addresses.try_next().await? {
+        if msg.header.family != family
+            || msg.header.scope != AddressScope::Universe
+            || msg.header.flags.contains(AddressHeaderFlags::Deprecated)
+        {
+            continue;
+        }
+
+        for attr in &msg.attributes {
+            if let AddressAttribute::Address(addr) = attr {
+                if !msg.header.flags.contains(AddressHeaderFlags::Secondary) {
+                    return Ok(addr.to_string());
+                }
+                fallback.get_or_insert_with(|| addr.to_string());
+            }
+        }
+    }
+
+    fallback.ok_or_else(|| {
+        format!(
+            "no global-scope {} address found on interface {}",
+            record_type, interface
+        )
+        .into()
+    })
+}
+
+async fn resolve_ip(
+    record_type: RecordType,
+    ip_source: &IpSource,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match ip_source {
+        IpSource::Http => match record_type {
+            RecordType::A => resolve_ipv4().await,
+            RecordType::AAAA => resolve_ipv6().await,
+        },
+        IpSource::Netlink { interface } => resolve_from_netlink(interface, record_type).await,
+    }
+}
+
+// Resolve the public IP for every record type referenced anywhere in the
+// config, once, so that many records sharing a type only pay for one lookup.
+async fn resolve_ips(config: &Config) -> HashMap<RecordType, Result<String, String>> {
+    let mut types = Vec::new();
+    for zone in &config.zones {
+        for record in &zone.records {
+            for &record_type in &record.record_types {
+                if !types.contains(&record_type) {
+                    types.push(record_type);
+                }
+            }
+        }
+    }
+
+    let mut ips = HashMap::new();
+    for record_type in types {
+        let result = resolve_ip(record_type, &config.ip_source)
+            .await
+            .map_err(|err| err.to_string());
+        ips.insert(record_type, result);
+    }
+
+    ips
+}
+
+// Build the payload shared by both the create (POST) and update (PATCH) paths.
+fn build_payload(record: &RecordConfig, record_type: RecordType, ip: &str) -> Payload {
+    let data = json!({
+            "type": record_type.to_string(),
+            "name": record.name,
+            "content": ip,
+            "ttl": record.ttl,
+            "proxied": record.proxy
+    });
+
+    serde_json::from_value(data).unwrap()
+}
+
+// Interpret a Cloudflare API response shared by the create and update paths.
+async fn handle_dns_response(
+    response: reqwest::Response,
+    record_type: RecordType,
+    record_name: &str,
+    action: &str,
+) -> Result<(), String> {
+    let body = response
+        .text()
+        .await
+        .map_err(|err| format!("Failed to read response body: {}", err))?;
+
+    let json: Value = serde_json::from_str(&body).map_err(|err| err.to_string())?;
+
+    if json["success"].as_bool().unwrap_or(false) {
+        info!("{} record {} for {}.", record_type, action, record_name);
+        Ok(())
+    } else {
+        Err(format!("HTTP response: \n{:#?}", json))
+    }
+}
+
+// Create a new DNS record via POST, using the same payload shape as an update.
+async fn create_record(
+    client: &Client,
+    headers: &HeaderMap,
+    zone_identifier: &str,
+    record: &RecordConfig,
+    record_type: RecordType,
+    ip: &str,
+) -> Result<(), String> {
+    let payload = build_payload(record, record_type, ip);
+
+    let response = client
+        .post(format!(
+            "https://api.cloudflare.com/client/v4/zones/{}/dns_records",
+            zone_identifier
+        ))
+        .headers(headers.clone())
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|err| format!("Failed to send request: {}", err))?;
+
+    handle_dns_response(response, record_type, &record.name, "created").await
+}
+
+// Sync a single record/type pair against Cloudflare: fetch the existing
+// record, compare against the resolved IP, and PATCH if it has changed. If
+// the record doesn't exist and `create_if_missing` is set, create it instead.
+async fn sync_record(
+    client: &Client,
+    headers: &HeaderMap,
+    zone_identifier: &str,
+    record: &RecordConfig,
+    record_type: RecordType,
+    ip: &str,
+) -> Result<(), String> {
+    let response = client
+        .get(format!(
+            "https://api.cloudflare.com/client/v4/zones/{}/dns_records?type={}&name={}",
+            zone_identifier, record_type, record.name
+        ))
+        .headers(headers.clone())
+        .send()
+        .await
+        .map_err(|err| format!("Failed to send request: {}", err))?;
+
+    let body = response
+        .text()
+        .await
+        .map_err(|err| format!("Failed to read response body: {}", err))?;
+
+    if body.contains("\"count\":0") {
+        if record.create_if_missing {
+            return create_record(client, headers, zone_identifier, record, record_type, ip).await;
+        }
+
+        return Err(format!(
+            "{} record does not exist, perhaps create one first? ({} for {})",
+            record_type, ip, record.name
+        ));
+    }
+
+    let json: Value = serde_json::from_str(&body).map_err(|err| err.to_string())?;
+
+    if let Some(content) = json["result"][0]["content"].as_str() {
+        if ip == content {
+            info!(
+                "{} ({}) for {} has not changed.",
+                record_type, ip, record.name
+            );
+            return Ok(());
+        }
     }
 
+    let record_identifier = json["result"][0]["id"]
+        .as_str()
+        .ok_or_else(|| "could not extract record id from JSON response".to_owned())?
+        .to_owned();
+
+    let payload = build_payload(record, record_type, ip);
+
+    let response = client
+        .patch(format!(
+            "https://api.cloudflare.com/client/v4/zones/{}/dns_records/{}",
+            zone_identifier, record_identifier
+        ))
+        .headers(headers.clone())
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|err| format!("Failed to send request: {}", err))?;
+
+    handle_dns_response(response, record_type, &record.name, "updated").await
+}
+
+// Run a single update pass over every configured zone/record. `last_seen`
+// caches the last IP each individual (zone, record, type) was successfully
+// synced to, so an unchanged address short-circuits before any Cloudflare
+// request is made — but only for records that actually succeeded last time.
+// A record is only marked up to date once `sync_record` confirms it, so a
+// failure (API error, missing record with `create_if_missing` off, ...) keeps
+// that record eligible for retry on the next tick even if the IP hasn't
+// moved again. Returns the list of failures encountered.
+async fn run_once(
+    config: &Config,
+    client: &Client,
+    headers: &HeaderMap,
+    last_seen: &mut HashMap<(String, String, RecordType), String>,
+) -> Vec<String> {
+    let ips = resolve_ips(config).await;
+
+    let mut failures = Vec::new();
+
+    for zone in &config.zones {
+        for record in &zone.records {
+            for &record_type in &record.record_types {
+                let ip = match ips.get(&record_type) {
+                    Some(Ok(ip)) => ip,
+                    Some(Err(err)) => {
+                        failures.push(format!(
+                            "{} for {}: failed to resolve a valid {} address: {}",
+                            record_type, record.name, record_type, err
+                        ));
+                        continue;
+                    }
+                    None => continue,
+                };
+
+                let key = (zone.zone_identifier.clone(), record.name.clone(), record_type);
+
+                if last_seen.get(&key).map(String::as_str) == Some(ip.as_str()) {
+                    continue;
+                }
+
+                match sync_record(
+                    client,
+                    headers,
+                    &zone.zone_identifier,
+                    record,
+                    record_type,
+                    ip,
+                )
+                .await
+                {
+                    Ok(()) => {
+                        last_seen.insert(key, ip.clone());
+                    }
+                    Err(err) => {
+                        failures.push(format!("{} for {}: {}", record_type, record.name, err));
+                    }
+                }
+            }
+        }
+    }
+
+    failures
+}
+
+// Parse `--daemon` and `--interval <seconds>` from argv. The interval flag
+// implies daemon mode even without `--daemon`.
+fn parse_daemon_args() -> (bool, Option<u64>) {
+    let mut daemon = false;
+    let mut interval = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--daemon" => daemon = true,
+            "--interval" => {
+                interval = args.next().and_then(|value| value.parse().ok());
+                daemon = true;
+            }
+            _ => {}
+        }
+    }
+
+    (daemon, interval)
+}
+
+// Log to the systemd journal (with proper priority fields) when stdout is
+// connected to it, otherwise fall back to plain stderr formatting.
+fn init_logging() {
+    if systemd_journal_logger::connected_to_journal() {
+        systemd_journal_logger::JournalLog::new()
+            .expect("Failed to initialize the journal logger")
+            .install()
+            .expect("Failed to install the journal logger");
+    } else {
+        env_logger::Builder::new()
+            .filter_level(LevelFilter::Info)
+            .format(|buf, record| writeln!(buf, "[{}] {}", record.level(), record.args()))
+            .init();
+    }
+
+    log::set_max_level(LevelFilter::Info);
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    init_logging();
+
+    let config_path = format!(
+        "{}/.config/ddns/config.json",
+        std::env::var("HOME").unwrap()
+    );
+    let config_contents = fs::read_to_string(&config_path).expect(&format!(
+        "Could not read the config file.\nconfig: {}",
+        config_path
+    ));
+    let config: Config = serde_json::from_str(&config_contents)
+        .expect(&format!("Invalid config file.\nconfig: {}", config_path));
+
     let client = Client::new();
 
     // Build the request headers
@@ -108,8 +506,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             );
         }
         _ => {
-            println!("The authentication method should either be global or token.\nExpected: \n....\n\"auth_method\": \"global\" or \"token\",\n....");
-            println!(
+            error!("The authentication method should either be global or token.\nExpected: \n....\n\"auth_method\": \"global\" or \"token\",\n....");
+            error!(
                 "\nFound: \n....\n\"auth_method\": {},\n....",
                 config.auth_method
             );
@@ -118,79 +516,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     headers.insert("Content-Type", HeaderValue::from_static("application/json"));
 
-    // Build the GET request and execute it
-    let response = client
-        .get(format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/dns_records?type=A&name={}",
-            config.zone_identifier, config.record_name
-        ))
-        .headers(headers.clone())
-        .send()
-        .await
-        .expect("Failed to send request");
-
-    // Read the response body
-    let record = response.text().await.expect("Failed to read response body");
-
-    if record.contains("\"count\":0") {
-        eprintln!(
-            "ddns updater: Record does not exist, perhaps create one first? ({} for {})",
-            &ip, &config.record_name
-        );
-        std::process::exit(1);
-    }
+    let (daemon, interval_arg) = parse_daemon_args();
+    let interval = interval_arg
+        .or(config.interval_seconds)
+        .unwrap_or(DEFAULT_INTERVAL_SECONDS);
 
-    let json: Value = serde_json::from_str(&record)?;
+    let mut last_seen = HashMap::new();
 
-    if let Some(content) = json["result"][0]["content"].as_str() {
-        let current_ip = content.to_owned();
+    if !daemon {
+        let failures = run_once(&config, &client, &headers, &mut last_seen).await;
 
-        if ip == current_ip {
-            println!(
-                "ddns updater: IP ({}) for {} has not changed.",
-                ip, &config.record_name
-            );
-            std::process::exit(0);
+        if !failures.is_empty() {
+            for failure in &failures {
+                warn!("{}", failure);
+            }
+            std::process::exit(1);
         }
+
+        return Ok(());
     }
 
-    let record_identifier = if let Some(content) = json["result"][0]["id"].as_str() {
-        content.to_owned()
-    } else {
-        return Err("Error: could not extract content from JSON response".into());
-    };
+    info!("running as a daemon (interval: {}s)", interval);
 
-    let data = json!({
-            "type": "A",
-            "name": config.record_name,
-            "content": ip,
-            "ttl": config.ttl,
-            "proxied": config.proxy
-    });
+    loop {
+        let failures = run_once(&config, &client, &headers, &mut last_seen).await;
 
-    let payload: Payload = serde_json::from_value(data).unwrap();
+        for failure in &failures {
+            warn!("{}", failure);
+        }
 
-    let response = client
-        .patch(format!(
-            "https://api.cloudflare.com/client/v4/zones/{}/dns_records/{}",
-            &config.zone_identifier, record_identifier
-        ))
-        .headers(headers)
-        .json(&payload)
-        .send()
-        .await
-        .expect("Failed to read response body")
-        .text()
-        .await?;
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
 
-    let json: Value = serde_json::from_str(&response)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if json["success"].as_bool().unwrap_or(false) {
-        println!("DNS updated.");
-    } else {
-        eprintln!("Error: HTTP response: \n{:#?}", json);
-        return Ok(());
-    };
+    fn ipv6_regex() -> Regex {
+        Regex::new(&format!("^(?:{})$", IPV6_REGEX)).unwrap()
+    }
 
-    Ok(())
+    #[test]
+    fn ipv6_regex_accepts_valid_addresses() {
+        let re = ipv6_regex();
+        assert!(re.is_match("2001:db8::8a2e:370:7334"));
+        assert!(re.is_match("2001:0db8:0000:0000:0000:8a2e:0370:7334"));
+        assert!(re.is_match("::1"));
+        assert!(re.is_match("::"));
+        assert!(re.is_match("fe80::1"));
+    }
+
+    #[test]
+    fn ipv6_regex_rejects_garbage() {
+        let re = ipv6_regex();
+        assert!(!re.is_match("garbage text with :: inside"));
+        assert!(!re.is_match("xyz::"));
+        assert!(!re.is_match("hello::world:::zzzz"));
+        assert!(!re.is_match(""));
+        assert!(!re.is_match("not-an-ip"));
+    }
 }